@@ -3,47 +3,51 @@ use std::fs::File;
 use std::future::Future;
 use std::io::{Read, Seek, Write};
 use std::path::Path;
-use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
 use std::{fs, io};
 
 use anyhow::Result;
 use secrecy::{SecretString, SecretVec};
-use tokio::io::{AsyncRead, AsyncSeekExt, AsyncWriteExt};
+use tokio::io::AsyncRead;
 
 use rencfs::crypto;
-use rencfs::crypto::writer::{AsyncSeekCryptoWriter, CryptoWriter};
-use rencfs::crypto::Cipher;
+use rencfs::crypto::writer::CryptoWriter;
+use rencfs::crypto::{ChecksumAlgorithm, Cipher, Kdf};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let password = SecretString::new("password".to_string());
     let salt = crypto::hash_secret_string(&password);
-    let cipher = Cipher::ChaCha20;
-    let key = Arc::new(crypto::derive_key(&password, cipher, salt)?);
 
     let mut args = args();
     let _ = args.next(); // skip the program name
     let path_in = args.next().expect("path_in is missing");
-    let path_out = format!(
-        "/tmp/{}.enc",
-        Path::new(&path_in).file_name().unwrap().to_str().unwrap()
-    );
-    let out = Path::new(&path_out).to_path_buf();
-    if out.exists() {
-        fs::remove_file(&out)?;
-    }
 
-    stream_speed(&path_in, &path_out, cipher, key.clone())?;
-    println!();
-    file_speed(&path_in, &path_out, cipher, key.clone())?;
-    println!();
-    let dir_path_out = format!(
-        "/tmp/{}.dir.enc",
-        Path::new(&path_in).file_name().unwrap().to_str().unwrap()
-    );
-    chunks_speed(&path_in, &dir_path_out, cipher, key.clone())?;
+    for cipher in [Cipher::ChaCha20, Cipher::Aes256Gcm] {
+        println!("=== {cipher:?} ===");
+        let key = Arc::new(crypto::derive_key(&password, cipher, salt.clone())?);
+
+        let path_out = format!(
+            "/tmp/{}.enc",
+            Path::new(&path_in).file_name().unwrap().to_str().unwrap()
+        );
+        let out = Path::new(&path_out).to_path_buf();
+        if out.exists() {
+            fs::remove_file(&out)?;
+        }
+
+        stream_speed(&path_in, &path_out, cipher, key.clone())?;
+        println!();
+        file_speed(&path_in, &path_out, cipher, &password)?;
+        println!();
+        let dir_path_out = format!(
+            "/tmp/{}.dir.enc",
+            Path::new(&path_in).file_name().unwrap().to_str().unwrap()
+        );
+        chunks_speed(&path_in, &dir_path_out, cipher, key.clone())?;
+        println!();
+    }
 
     Ok(())
 }
@@ -63,6 +67,7 @@ where
     Ok(())
 }
 
+#[allow(dead_code)]
 async fn speed_async<F>(f: F, label: &str, size: u64) -> Result<()>
 where
     F: Future<Output = Result<()>>,
@@ -85,6 +90,7 @@ fn check_hash(r1: &mut impl Read, r2: &mut (impl Read + ?Sized)) -> Result<()> {
     Ok(())
 }
 
+#[allow(dead_code)]
 async fn check_hash_async<R: AsyncRead + Unpin, FR>(
     r1: &mut (impl AsyncRead + ?Sized + Unpin),
     r2: &mut FR,
@@ -118,35 +124,23 @@ fn stream_speed(
     Ok(())
 }
 
-fn file_speed(
-    path_in: &str,
-    path_out: &str,
-    cipher: Cipher,
-    key: Arc<SecretVec<u8>>,
-) -> Result<()> {
+fn file_speed(path_in: &str, path_out: &str, cipher: Cipher, password: &SecretString) -> Result<()> {
     println!("file speed");
     let _ = fs::remove_file(path_out);
     let mut file_in = File::open(path_in)?;
-    let mut writer = crypto::create_tmp_file_writer(
-        &Path::new(&path_out).to_path_buf(),
-        &Path::new(&"/tmp").to_path_buf(),
+    let mut writer = crypto::create_tmp_file_writer_with_password(
+        Path::new(&path_out),
+        Path::new("/tmp"),
         cipher,
-        key.clone(),
-        42_u64,
-        None,
-        None,
+        password,
+        Kdf::default(),
         None,
+        Some(ChecksumAlgorithm::Blake3),
     )?;
     let size = file_in.metadata()?.len();
     let f = || {
-        crypto::create_file_reader(
-            &Path::new(&path_out).to_path_buf(),
-            cipher,
-            key.clone(),
-            42_u64,
-            None,
-        )
-        .unwrap()
+        crypto::create_file_reader_with_password(Path::new(&path_out), cipher, password, None)
+            .unwrap()
     };
     test_speed(&mut file_in, &mut *writer, size, f)?;
     file_in.seek(io::SeekFrom::Start(0)).unwrap();
@@ -165,19 +159,20 @@ fn chunks_speed(
     let _ = fs::remove_dir_all(path_out);
     let mut file_in = File::open(path_in)?;
     let mut writer = crypto::create_chunked_tmp_file_writer(
-        &Path::new(&path_out).to_path_buf(),
-        &Path::new(&"/tmp").to_path_buf(),
+        Path::new(&path_out),
+        Path::new("/tmp"),
         cipher,
         key.clone(),
         42_u64,
         None,
         None,
         None,
+        Some(ChecksumAlgorithm::Crc32),
     )?;
     let size = file_in.metadata()?.len();
     let f = || {
         crypto::create_chunked_file_reader(
-            &Path::new(&path_out).to_path_buf(),
+            Path::new(&path_out),
             cipher,
             key.clone(),
             42_u64,
@@ -186,6 +181,14 @@ fn chunks_speed(
         .unwrap()
     };
     test_speed(&mut file_in, &mut *writer, size, f)?;
+    let (stored, deduped) = writer.dedup_stats();
+    let total = stored + deduped;
+    if total > 0 {
+        println!(
+            "dedup: {deduped}/{total} chunks reused ({:.1}%)",
+            100.0 * deduped as f64 / total as f64
+        );
+    }
     file_in.seek(io::SeekFrom::Start(0)).unwrap();
     check_hash(&mut file_in, &mut *f())?;
     fs::remove_dir_all(path_out)?;
@@ -0,0 +1,183 @@
+//! Content-addressed storage for encrypted chunks, shared by
+//! [`super::writer::ChunkedFileCryptoWriter`] and
+//! [`super::reader::create_chunked_file_reader`].
+//!
+//! Each unique plaintext chunk is identified by its BLAKE3 hash and stored
+//! at most once, under `<dir_path>/chunks/<hex id>.chunk`, encrypted with a
+//! nonce seed derived from that id rather than the chunk's position in the
+//! file. That makes the stored ciphertext itself content-addressed: two
+//! chunks with identical plaintext always produce the identical encrypted
+//! file, so re-encrypting a file that shares chunks with a previous
+//! encryption into the same store can skip re-encrypting and rewriting them
+//! and just record a reference.
+
+use std::fs::{self, File};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use secrecy::SecretVec;
+
+use super::writer::{create_writer, CryptoWriter};
+use super::Cipher;
+
+/// Content id of a chunk: the BLAKE3 hash of its plaintext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ChunkId([u8; 32]);
+
+impl ChunkId {
+    pub(super) fn of(plaintext: &[u8]) -> Self {
+        ChunkId(*blake3::hash(plaintext).as_bytes())
+    }
+
+    pub(super) fn to_hex(self) -> String {
+        hex::encode(self.0)
+    }
+
+    pub(super) fn from_hex(hex_str: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_str)?;
+        let id: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("chunk id is not 32 bytes"))?;
+        Ok(ChunkId(id))
+    }
+
+    /// Nonce seed derived from the content id itself, rather than the
+    /// chunk's position, so identical plaintext always encrypts to
+    /// identical ciphertext under a given key and can be deduplicated.
+    pub(super) fn nonce_seed(self) -> u64 {
+        u64::from_le_bytes(self.0[..8].try_into().unwrap())
+    }
+}
+
+/// A directory of content-addressed, encrypted chunk files, shared across
+/// however many times a file is (re-)encrypted into the same chunk store.
+pub(super) struct ChunkStore {
+    chunks_dir: PathBuf,
+}
+
+impl ChunkStore {
+    pub(super) fn open(dir_path: &Path) -> io::Result<Self> {
+        let chunks_dir = dir_path.join("chunks");
+        fs::create_dir_all(&chunks_dir)?;
+        Ok(Self { chunks_dir })
+    }
+
+    fn path_for(&self, id: ChunkId) -> PathBuf {
+        self.chunks_dir.join(format!("{}.chunk", id.to_hex()))
+    }
+
+    pub(super) fn contains(&self, id: ChunkId) -> bool {
+        self.path_for(id).is_file()
+    }
+
+    /// Encrypts and stores `plaintext` under `id`, unless a chunk with that
+    /// id is already present, staging into `tmp_dir` and renaming into place
+    /// so a reader never observes a partially-written chunk. Returns
+    /// whether a new chunk was written (`false` means it was already known
+    /// and nothing was done).
+    pub(super) fn store(
+        &self,
+        id: ChunkId,
+        plaintext: &[u8],
+        cipher: Cipher,
+        key: &Arc<SecretVec<u8>>,
+        tmp_dir: &Path,
+    ) -> io::Result<bool> {
+        if self.contains(id) {
+            return Ok(false);
+        }
+        fs::create_dir_all(tmp_dir)?;
+        let tmp_path = tmp_dir.join(format!("{}.chunk.tmp", id.to_hex()));
+        let file = File::create(&tmp_path)?;
+        let mut writer = create_writer(file, cipher, key.clone(), id.nonce_seed());
+        writer.write_all(plaintext)?;
+        writer.finish()?;
+        fs::rename(&tmp_path, self.path_for(id))?;
+        Ok(true)
+    }
+
+    pub(super) fn open_chunk(&self, id: ChunkId) -> io::Result<File> {
+        File::open(self.path_for(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storing_identical_plaintext_twice_is_a_no_op_the_second_time() {
+        let dir = std::env::temp_dir().join("rencfs-test-chunk-store-store");
+        let tmp_dir = dir.join("tmp");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+
+        let store = ChunkStore::open(&dir).unwrap();
+        let key = Arc::new(SecretVec::new(vec![0x01_u8; 32]));
+        let plaintext = b"identical chunk content";
+        let id = ChunkId::of(plaintext);
+
+        assert!(store
+            .store(id, plaintext, Cipher::ChaCha20, &key, &tmp_dir)
+            .unwrap());
+        assert!(!store
+            .store(id, plaintext, Cipher::ChaCha20, &key, &tmp_dir)
+            .unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn re_encrypting_a_file_with_shared_chunks_reuses_them() {
+        use crate::crypto::writer::create_chunked_tmp_file_writer;
+
+        let dir = std::env::temp_dir().join("rencfs-test-chunk-store-dedup");
+        let store_dir = dir.join("store");
+        let tmp_dir = dir.join("tmp");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+
+        let key = Arc::new(SecretVec::new(vec![0x02_u8; 32]));
+        let plaintext = vec![0x5A_u8; 256 * 1024];
+
+        let mut first = create_chunked_tmp_file_writer(
+            &store_dir,
+            &tmp_dir,
+            Cipher::ChaCha20,
+            key.clone(),
+            1,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        first.write_all(&plaintext).unwrap();
+        first.finish().unwrap();
+        let (stored, deduped) = first.dedup_stats();
+        let total_chunks = stored + deduped;
+        assert!(stored > 0);
+
+        let mut second = create_chunked_tmp_file_writer(
+            &store_dir,
+            &tmp_dir,
+            Cipher::ChaCha20,
+            key,
+            2,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        second.write_all(&plaintext).unwrap();
+        second.finish().unwrap();
+        let (stored_again, deduped_again) = second.dedup_stats();
+        assert_eq!(stored_again, 0);
+        assert_eq!(deduped_again, total_chunks);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+//! Content-defined chunking (FastCDC) used by the chunked file writer/reader
+//! so that chunk boundaries track content rather than byte offset: inserting
+//! or deleting a byte only reshuffles the chunk(s) around the edit instead of
+//! every chunk downstream of it.
+
+use once_cell::sync::Lazy;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Fixed seed so the `Gear` table (and therefore chunk boundaries for a given
+/// input) is identical across processes and platforms.
+const GEAR_SEED: u64 = 0x7265_6e63_6673_3031;
+
+static GEAR: Lazy<[u64; 256]> = Lazy::new(|| {
+    let mut rng = ChaCha8Rng::seed_from_u64(GEAR_SEED);
+    let mut table = [0_u64; 256];
+    for slot in table.iter_mut() {
+        *slot = rng.gen();
+    }
+    table
+});
+
+/// Size thresholds driving FastCDC's normalized chunking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FastCdcConfig {
+    pub min_size: usize,
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for FastCdcConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 16 * 1024,
+            avg_size: 32 * 1024,
+            max_size: 64 * 1024,
+        }
+    }
+}
+
+/// Which chunking strategy a chunked writer should use to decide chunk
+/// boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkerConfig {
+    /// Cut every `n` bytes, regardless of content.
+    FixedSize(usize),
+    /// Content-defined chunking driven by a rolling Gear hash.
+    FastCdc(FastCdcConfig),
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        ChunkerConfig::FastCdc(FastCdcConfig::default())
+    }
+}
+
+/// Byte-at-a-time cut-point detector. Callers feed plaintext bytes one at a
+/// time via [`Chunker::push`]; when it returns `true` the byte just pushed is
+/// the last byte of the current chunk.
+///
+/// Driving this byte-by-byte (rather than scanning a fully buffered slice)
+/// means boundaries only depend on the logical byte stream, not on how the
+/// caller happened to batch its `write()` calls.
+pub enum Chunker {
+    FixedSize { size: usize, pos: usize },
+    FastCdc(FastCdcChunker),
+}
+
+impl Chunker {
+    pub fn new(config: ChunkerConfig) -> Self {
+        match config {
+            ChunkerConfig::FixedSize(size) => Chunker::FixedSize { size, pos: 0 },
+            ChunkerConfig::FastCdc(cfg) => Chunker::FastCdc(FastCdcChunker::new(cfg)),
+        }
+    }
+
+    /// Feed the next plaintext byte. Returns `true` if this byte ends the
+    /// current chunk.
+    pub fn push(&mut self, byte: u8) -> bool {
+        match self {
+            Chunker::FixedSize { size, pos } => {
+                *pos += 1;
+                if *pos >= *size {
+                    *pos = 0;
+                    true
+                } else {
+                    false
+                }
+            }
+            Chunker::FastCdc(c) => c.push(byte),
+        }
+    }
+
+    /// Resets internal state so the next `push` starts a fresh chunk. Must be
+    /// called by the caller right after a `push` returned `true`.
+    pub fn start_new_chunk(&mut self) {
+        if let Chunker::FastCdc(c) = self {
+            c.reset();
+        }
+    }
+}
+
+/// FastCDC cut-point detector for a single chunking config.
+pub struct FastCdcChunker {
+    config: FastCdcConfig,
+    mask_s: u64,
+    mask_l: u64,
+    fp: u64,
+    pos: usize,
+}
+
+impl FastCdcChunker {
+    pub fn new(config: FastCdcConfig) -> Self {
+        let bits = (config.avg_size.max(2) as f64).log2().round() as u32;
+        // Stricter mask (more one-bits -> lower cut probability) while below
+        // AVG, looser mask (fewer one-bits -> higher cut probability) once
+        // past it, so chunk sizes normalize around AVG instead of spreading
+        // out across [MIN, MAX].
+        let bits_s = (bits + 2).min(63);
+        let bits_l = bits.saturating_sub(2).max(1);
+        Self {
+            config,
+            mask_s: (1_u64 << bits_s) - 1,
+            mask_l: (1_u64 << bits_l) - 1,
+            fp: 0,
+            pos: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.fp = 0;
+        self.pos = 0;
+    }
+
+    /// Feed the next plaintext byte. Returns `true` if this byte ends the
+    /// current chunk; the caller must then call [`Self::reset`].
+    pub fn push(&mut self, byte: u8) -> bool {
+        self.pos += 1;
+
+        if self.pos <= self.config.min_size {
+            // Never cut before MIN.
+            return self.pos >= self.config.max_size;
+        }
+
+        self.fp = (self.fp << 1).wrapping_add(GEAR[byte as usize]);
+        let mask = if self.pos < self.config.avg_size {
+            self.mask_s
+        } else {
+            self.mask_l
+        };
+
+        self.fp & mask == 0 || self.pos >= self.config.max_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_lengths(data: &[u8], config: FastCdcConfig) -> Vec<usize> {
+        let mut chunker = FastCdcChunker::new(config);
+        let mut lengths = Vec::new();
+        let mut current = 0_usize;
+        for &byte in data {
+            current += 1;
+            if chunker.push(byte) {
+                lengths.push(current);
+                current = 0;
+                chunker.reset();
+            }
+        }
+        if current > 0 {
+            lengths.push(current);
+        }
+        lengths
+    }
+
+    #[test]
+    fn boundaries_are_deterministic_for_identical_content() {
+        let data: Vec<u8> = (0..300_000).map(|i| (i % 251) as u8).collect();
+        let config = FastCdcConfig::default();
+        assert_eq!(chunk_lengths(&data, config), chunk_lengths(&data, config));
+    }
+
+    #[test]
+    fn chunk_sizes_stay_within_bounds() {
+        let data: Vec<u8> = (0..500_000).map(|i| (i % 7) as u8).collect();
+        let config = FastCdcConfig::default();
+        let lengths = chunk_lengths(&data, config);
+        for (i, len) in lengths.iter().enumerate() {
+            let is_last = i == lengths.len() - 1;
+            assert!(*len <= config.max_size, "chunk {i} exceeded MAX: {len}");
+            if !is_last {
+                assert!(*len >= config.min_size, "chunk {i} below MIN: {len}");
+            }
+        }
+    }
+
+    fn pseudo_random_bytes(n: usize) -> Vec<u8> {
+        let mut state: u64 = 0x1234_5678_9abc_def0;
+        (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state & 0xff) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn insertion_only_reshuffles_nearby_chunks() {
+        let base = pseudo_random_bytes(300_000);
+        let config = FastCdcConfig::default();
+        let before = chunk_lengths(&base, config);
+
+        let mut edited = base.clone();
+        edited.insert(150_000, 0xAB);
+        let after = chunk_lengths(&edited, config);
+
+        // Chunks entirely before the edit point are untouched; only the
+        // chunk containing it (and possibly its neighbor) should change.
+        let mut unaffected = 0;
+        let mut offset = 0;
+        for len in &before {
+            if offset + len > 150_000 {
+                break;
+            }
+            offset += len;
+            unaffected += 1;
+        }
+        assert!(unaffected >= 3, "expected several untouched leading chunks");
+        assert_eq!(before[..unaffected], after[..unaffected]);
+    }
+}
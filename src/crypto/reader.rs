@@ -0,0 +1,375 @@
+//! Readers that transparently decrypt whatever they read: a plain stream
+//! reader, a file reader, and a chunked reader that reassembles the chunk
+//! files written by [`super::writer::create_chunked_tmp_file_writer`].
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use secrecy::SecretVec;
+
+use super::aead_cipher::AeadCipher;
+use super::chunk_store::{ChunkId, ChunkStore};
+use super::integrity::{load_entries, sidecar_path, ChecksumVerifier};
+use super::writer::{
+    chunk_nonce, derive_stream_key, stream_sub_chunk_lens, STREAM_CHUNK_CIPHERTEXT_SIZE,
+};
+use super::Cipher;
+
+/// Decrypts an authenticated STREAM (see
+/// [`super::writer::StreamCryptoWriter`]) read from the wrapped reader `R`,
+/// verifying each chunk's AEAD tag and the last-chunk flag as it goes.
+/// The first byte of the stream is the cipher-id header written by
+/// [`super::writer::StreamCryptoWriter`]; it's checked against the expected
+/// `cipher` before any chunk is decrypted, so a mismatched cipher is
+/// reported clearly instead of producing garbage plaintext.
+pub struct StreamCryptoReader<R: Read> {
+    inner: R,
+    expected_cipher: Cipher,
+    key: [u8; 32],
+    cipher: Option<AeadCipher>,
+    counter: u64,
+    /// A single byte read past a full-size chunk to tell whether it was the
+    /// last one; stashed here to be the first byte of the next chunk if not.
+    pending: Option<u8>,
+    plaintext: Vec<u8>,
+    pos: usize,
+    finished: bool,
+    checksums: Option<ChecksumVerifier>,
+}
+
+impl<R: Read> StreamCryptoReader<R> {
+    fn new(inner: R, cipher: Cipher, key: &SecretVec<u8>, nonce_seed: u64) -> Self {
+        let subkey = derive_stream_key(key, nonce_seed);
+        Self {
+            inner,
+            expected_cipher: cipher,
+            key: subkey,
+            cipher: None,
+            counter: 0,
+            pending: None,
+            plaintext: Vec::new(),
+            pos: 0,
+            finished: false,
+            checksums: None,
+        }
+    }
+
+    /// Attaches a checksum verifier that checks each chunk's plaintext,
+    /// in order, as it's decrypted.
+    pub(super) fn enable_checksum_verification(&mut self, verifier: ChecksumVerifier) {
+        self.checksums = Some(verifier);
+    }
+
+    /// Reads the one-byte cipher-id header on first use and checks it
+    /// against the cipher the caller asked for.
+    fn ensure_header(&mut self) -> io::Result<()> {
+        if self.cipher.is_some() {
+            return Ok(());
+        }
+        let mut id = [0_u8; 1];
+        self.inner.read_exact(&mut id).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                io::Error::other("STREAM ended before the cipher header was read")
+            } else {
+                e
+            }
+        })?;
+        let actual = super::Cipher::from_id(id[0])
+            .map_err(|e| io::Error::other(format!("invalid cipher header: {e}")))?;
+        if actual != self.expected_cipher {
+            return Err(io::Error::other(format!(
+                "cipher mismatch: stream was encrypted with {actual:?}, expected {:?}",
+                self.expected_cipher
+            )));
+        }
+        self.cipher = Some(AeadCipher::new(actual, &self.key));
+        Ok(())
+    }
+
+    /// Reads and authenticates the next STREAM chunk into `self.plaintext`.
+    fn fill_next_chunk(&mut self) -> io::Result<()> {
+        self.ensure_header()?;
+
+        let mut chunk = Vec::with_capacity(STREAM_CHUNK_CIPHERTEXT_SIZE);
+        if let Some(b) = self.pending.take() {
+            chunk.push(b);
+        }
+        read_up_to(&mut self.inner, &mut chunk, STREAM_CHUNK_CIPHERTEXT_SIZE)?;
+
+        if chunk.is_empty() {
+            return Err(io::Error::other(
+                "STREAM ended without a final chunk (truncated or tampered ciphertext)",
+            ));
+        }
+
+        // A short read always ends the stream. A full-size read is
+        // ambiguous (it could be a full continuation chunk or a final
+        // chunk that happens to be exactly STREAM_CHUNK_SIZE), so peek one
+        // more byte: if there's nothing after it, this chunk was the last.
+        let is_last = if chunk.len() == STREAM_CHUNK_CIPHERTEXT_SIZE {
+            let mut extra = [0_u8; 1];
+            if self.inner.read(&mut extra)? == 1 {
+                self.pending = Some(extra[0]);
+                false
+            } else {
+                true
+            }
+        } else {
+            true
+        };
+
+        let nonce = chunk_nonce(self.counter, is_last);
+        let plaintext = self
+            .cipher
+            .as_ref()
+            .expect("header checked above")
+            .decrypt(&nonce, chunk.as_slice())
+            .map_err(|_| {
+                io::Error::other(format!(
+                    "STREAM authentication failed at chunk {}",
+                    self.counter
+                ))
+            })?;
+
+        if let Some(verifier) = &mut self.checksums {
+            verifier.verify_next(&plaintext)?;
+        }
+
+        if is_last {
+            self.finished = true;
+        } else {
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| io::Error::other("STREAM chunk counter overflow"))?;
+        }
+        self.plaintext = plaintext;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StreamCryptoReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.plaintext.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.fill_next_chunk()?;
+        }
+        let n = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..n].copy_from_slice(&self.plaintext[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Grows `buf` to `want` bytes total, reading from `r` to fill the
+/// difference and looping until either `want` is reached or EOF hits.
+fn read_up_to(r: &mut impl Read, buf: &mut Vec<u8>, want: usize) -> io::Result<()> {
+    let mut filled = buf.len();
+    buf.resize(want, 0);
+    while filled < want {
+        let read = r.read(&mut buf[filled..want])?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    buf.truncate(filled);
+    Ok(())
+}
+
+/// Creates a reader that decrypts `inner`'s contents, which must have been
+/// produced by [`super::writer::create_writer`] with the same cipher, key
+/// and nonce seed.
+pub fn create_reader<R: Read>(
+    inner: R,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    nonce_seed: u64,
+) -> StreamCryptoReader<R> {
+    StreamCryptoReader::new(inner, cipher, &key, nonce_seed)
+}
+
+/// Reads the preamble written by `create_tmp_file_writer`/
+/// `create_tmp_file_writer_with_password` ahead of the STREAM cipher-id
+/// header: the randomly generated nonce seed the file's STREAM subkey was
+/// derived from, and an optional KDF header (empty when the file was
+/// written from an already-derived key).
+fn read_preamble(file: &mut File) -> io::Result<(u64, Vec<u8>)> {
+    let mut seed_bytes = [0_u8; 8];
+    file.read_exact(&mut seed_bytes)?;
+    let nonce_seed = u64::from_le_bytes(seed_bytes);
+    let mut len_bytes = [0_u8; 4];
+    file.read_exact(&mut len_bytes)?;
+    let kdf_header_len = u32::from_le_bytes(len_bytes) as usize;
+    let mut kdf_header = vec![0_u8; kdf_header_len];
+    file.read_exact(&mut kdf_header)?;
+    Ok((nonce_seed, kdf_header))
+}
+
+/// Creates a reader for a file produced by `create_tmp_file_writer`. If a
+/// checksum sidecar (`<file_path>.sums`) was written alongside it, each
+/// chunk's plaintext is verified against it as it's decrypted.
+pub fn create_file_reader(
+    file_path: &Path,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    _lock: Option<Arc<RwLock<bool>>>,
+) -> Result<Box<dyn Read>> {
+    let mut file = File::open(file_path)?;
+    let (nonce_seed, kdf_header) = read_preamble(&mut file)?;
+    if !kdf_header.is_empty() {
+        return Err(io::Error::other(
+            "file was encrypted with a password-derived key; use create_file_reader_with_password",
+        )
+        .into());
+    }
+    let mut reader = create_reader(file, cipher, key, nonce_seed);
+    let sidecar = sidecar_path(file_path);
+    if sidecar.is_file() {
+        reader.enable_checksum_verification(ChecksumVerifier::new(load_entries(&sidecar)?));
+    }
+    Ok(Box::new(reader))
+}
+
+/// Creates a reader for a file produced by
+/// `create_tmp_file_writer_with_password`: reads the embedded KDF header
+/// from the file's preamble and calls
+/// [`super::kdf::derive_key_from_header`] with `password` to reconstruct
+/// the key, rather than taking an already-derived one.
+pub fn create_file_reader_with_password(
+    file_path: &Path,
+    cipher: Cipher,
+    password: &secrecy::SecretString,
+    _lock: Option<Arc<RwLock<bool>>>,
+) -> Result<Box<dyn Read>> {
+    let mut file = File::open(file_path)?;
+    let (nonce_seed, kdf_header) = read_preamble(&mut file)?;
+    if kdf_header.is_empty() {
+        anyhow::bail!("file has no embedded KDF header; use create_file_reader");
+    }
+    let (key, _consumed) = super::kdf::derive_key_from_header(password, cipher.key_len(), &kdf_header)?;
+    let mut reader = create_reader(file, cipher, Arc::new(key), nonce_seed);
+    let sidecar = sidecar_path(file_path);
+    if sidecar.is_file() {
+        reader.enable_checksum_verification(ChecksumVerifier::new(load_entries(&sidecar)?));
+    }
+    Ok(Box::new(reader))
+}
+
+/// Creates a reader for a directory produced by
+/// `create_chunked_tmp_file_writer`: reads the `index` file it wrote to get
+/// the ordered sequence of chunk ids making up the file, then lazily opens
+/// and decrypts each one in turn from the content-addressed chunk store. If
+/// a `checksums` sidecar is present, each chunk's plaintext is verified
+/// against it as it's decrypted.
+pub fn create_chunked_file_reader(
+    dir_path: &Path,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    _nonce_seed: u64,
+    _lock: Option<Arc<RwLock<bool>>>,
+) -> Result<Box<dyn Read>> {
+    let index_contents = std::fs::read_to_string(dir_path.join("index"))?;
+    let ids = index_contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (id, len) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow::anyhow!("malformed chunk index entry"))?;
+            Ok((ChunkId::from_hex(id)?, len.parse::<u64>()?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let store = ChunkStore::open(dir_path)?;
+
+    let checksums_path = dir_path.join("checksums");
+    let checksums = checksums_path
+        .is_file()
+        .then(|| load_entries(&checksums_path))
+        .transpose()?;
+
+    Ok(Box::new(ChainedChunkReader {
+        store,
+        cipher,
+        key,
+        ids: ids.into_iter(),
+        checksums,
+        current: None,
+    }))
+}
+
+/// Lazily opens and decrypts each chunk in `ids`, in order, as the previous
+/// one is exhausted, rather than eagerly opening every file descriptor up
+/// front. Each id is paired with its plaintext length, which is also the
+/// length `ChunkedFileCryptoWriter::flush_chunk` recorded checksums against:
+/// `checksums`, when present, holds one entry per *STREAM sub-chunk* across
+/// all chunks in the same order as `ids`, so for each chunk we pop off
+/// exactly as many entries as that chunk's own inner `StreamCryptoWriter`
+/// produced, rather than always one.
+struct ChainedChunkReader {
+    store: ChunkStore,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    ids: std::vec::IntoIter<(ChunkId, u64)>,
+    checksums: Option<std::collections::VecDeque<super::integrity::ChunkChecksumEntry>>,
+    current: Option<Box<dyn Read>>,
+}
+
+impl Read for ChainedChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.current.is_none() {
+                match self.ids.next() {
+                    None => return Ok(0),
+                    Some((id, len)) => {
+                        let file = self.store.open_chunk(id)?;
+                        let mut reader =
+                            create_reader(file, self.cipher, self.key.clone(), id.nonce_seed());
+                        if let Some(queue) = self.checksums.as_mut() {
+                            let sub_chunks = stream_sub_chunk_lens(len as usize).count();
+                            let entries: std::collections::VecDeque<_> =
+                                queue.drain(..sub_chunks.min(queue.len())).collect();
+                            reader.enable_checksum_verification(ChecksumVerifier::new(entries));
+                        }
+                        self.current = Some(Box::new(reader));
+                    }
+                }
+            }
+            let read = self.current.as_mut().unwrap().read(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+            self.current = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+    use crate::crypto::writer::{create_writer, CryptoWriter};
+    use crate::crypto::Cipher;
+
+    #[test]
+    fn cipher_mismatch_is_rejected() {
+        let key = Arc::new(SecretVec::new(vec![0x5A_u8; 32]));
+
+        let mut ciphertext = Vec::new();
+        let mut writer = create_writer(&mut ciphertext, Cipher::ChaCha20, key.clone(), 1);
+        writer.write_all(b"some plaintext").unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = create_reader(ciphertext.as_slice(), Cipher::Aes256Gcm, key, 1);
+        let mut decrypted = Vec::new();
+        let err = reader.read_to_end(&mut decrypted).unwrap_err();
+        assert!(err.to_string().contains("cipher mismatch"));
+    }
+}
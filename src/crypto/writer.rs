@@ -0,0 +1,685 @@
+//! Writers that transparently encrypt whatever is written to them: a plain
+//! stream writer, a file writer that stages into a temp file and renames
+//! into place on `finish`, and a chunked writer that splits its input across
+//! several independently-encrypted chunk files.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use rand::Rng;
+use secrecy::{ExposeSecret, SecretVec};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncSeek, AsyncWrite};
+
+use super::aead_cipher::AeadCipher;
+use super::chunk_store::{ChunkId, ChunkStore};
+use super::chunker::{Chunker, ChunkerConfig};
+use super::integrity::{sidecar_path, ChecksumAlgorithm, ChecksumRecorder};
+use super::Cipher;
+
+/// Size of a plaintext STREAM chunk. Chosen to match `FastCdcConfig`'s
+/// default MAX so a content-defined chunk and a STREAM chunk line up.
+pub(super) const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+/// A STREAM chunk on the wire: plaintext plus the 16-byte AEAD tag.
+pub(super) const STREAM_CHUNK_CIPHERTEXT_SIZE: usize = STREAM_CHUNK_SIZE + 16;
+
+/// Builds the per-chunk STREAM nonce: an 11-byte big-endian chunk counter
+/// followed by a 1-byte "is this the last chunk" flag, as in age's STREAM
+/// construction. Binding the flag into the nonce means truncating the
+/// ciphertext (dropping the real final chunk) or reordering chunks both fail
+/// authentication instead of silently decrypting.
+pub(super) fn chunk_nonce(counter: u64, last: bool) -> chacha20poly1305::Nonce {
+    let mut nonce = [0_u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce.into()
+}
+
+/// Lengths of the STREAM sub-chunks a [`StreamCryptoWriter`] splits
+/// `total_len` bytes into: zero or more full `STREAM_CHUNK_SIZE` chunks,
+/// always followed by one final chunk (which may be empty, if `total_len`
+/// is an exact multiple of `STREAM_CHUNK_SIZE`), matching exactly how
+/// [`StreamCryptoWriter::write`]/`finish` seal chunks. Used to record and
+/// verify per-chunk checksums at the same granularity they're actually
+/// written at, regardless of what larger unit (e.g. a content-defined
+/// chunk) the caller is splitting its input into.
+pub(super) fn stream_sub_chunk_lens(total_len: usize) -> impl Iterator<Item = usize> {
+    let full_chunks = total_len / STREAM_CHUNK_SIZE;
+    let remainder = total_len % STREAM_CHUNK_SIZE;
+    std::iter::repeat_n(STREAM_CHUNK_SIZE, full_chunks).chain(std::iter::once(remainder))
+}
+
+/// Derives a per-stream subkey from `key` and `nonce_seed` so that streams
+/// encrypted under the same master key never reuse a STREAM nonce sequence.
+///
+/// **`nonce_seed` must be unique per distinct plaintext encrypted under a
+/// given `key`.** Two streams sharing a `(key, nonce_seed)` pair derive the
+/// same subkey and therefore restart their chunk counter at the same nonce,
+/// which under an AEAD cipher leaks the XOR of the two plaintexts and breaks
+/// forgery resistance. `create_tmp_file_writer` picks a fresh random seed
+/// per file for exactly this reason; callers of the lower-level
+/// [`create_writer`] are responsible for passing a seed they know is unique
+/// (the content-addressed chunk store, for instance, derives it from the
+/// plaintext's own content hash, which is safe because identical input is
+/// the only way to get a repeat, and re-emitting identical ciphertext for
+/// identical plaintext is the point).
+pub(super) fn derive_stream_key(key: &SecretVec<u8>, nonce_seed: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(key.expose_secret());
+    hasher.update(b"rencfs-stream");
+    hasher.update(nonce_seed.to_le_bytes());
+    hasher.finalize().into()
+}
+
+/// A writer that encrypts everything written to it before forwarding it to
+/// the wrapped writer `W`.
+pub trait CryptoWriter<W: Write>: Write + Send + Sync {
+    /// Flushes any buffered plaintext and finalizes the ciphertext. Must be
+    /// called before the underlying writer is considered complete; dropping
+    /// without calling `finish` may leave an incomplete/unreadable result.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Async counterpart of [`CryptoWriter`] for writers that also support
+/// seeking (e.g. in-place re-encryption of part of a file).
+#[async_trait::async_trait]
+pub trait AsyncSeekCryptoWriter<W>: AsyncWrite + AsyncSeek + Send + Sync {
+    async fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Encrypts whatever is written to it as an authenticated STREAM (age-style):
+/// a one-byte cipher-id header is written first, then the plaintext is split
+/// into fixed-size chunks, each sealed with the chosen AEAD under a nonce
+/// built from the chunk's position and whether it's the last one, and the
+/// sealed chunks are forwarded to the inner writer `W`.
+pub struct StreamCryptoWriter<W: Write> {
+    inner: W,
+    cipher: AeadCipher,
+    buf: Vec<u8>,
+    counter: u64,
+    header_written: bool,
+    cipher_id: u8,
+    finished: bool,
+    checksums: Option<ChecksumRecorder>,
+}
+
+impl<W: Write> StreamCryptoWriter<W> {
+    fn new(inner: W, cipher: Cipher, key: &SecretVec<u8>, nonce_seed: u64) -> Self {
+        let subkey = derive_stream_key(key, nonce_seed);
+        Self {
+            inner,
+            cipher: AeadCipher::new(cipher, &subkey),
+            buf: Vec::with_capacity(STREAM_CHUNK_SIZE),
+            counter: 0,
+            header_written: false,
+            cipher_id: cipher.id(),
+            finished: false,
+            checksums: None,
+        }
+    }
+
+    /// Starts recording a per-chunk checksum with `algorithm` for every
+    /// chunk sealed from here on. Must be called before any data is
+    /// written. Retrieve the recorded checksums with [`Self::take_checksums`]
+    /// after `finish`.
+    pub(super) fn enable_checksums(&mut self, algorithm: ChecksumAlgorithm) {
+        self.checksums = Some(ChecksumRecorder::new(algorithm));
+    }
+
+    pub(super) fn take_checksums(&mut self) -> Option<ChecksumRecorder> {
+        self.checksums.take()
+    }
+
+    fn seal_and_write_chunk(&mut self, last: bool) -> io::Result<()> {
+        if let Some(recorder) = &mut self.checksums {
+            recorder.record(&self.buf);
+        }
+        if !self.header_written {
+            self.header_written = true;
+            self.inner.write_all(&[self.cipher_id])?;
+        }
+        let nonce = chunk_nonce(self.counter, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, self.buf.as_slice())
+            .map_err(|_| io::Error::other("STREAM encryption failed"))?;
+        self.inner.write_all(&ciphertext)?;
+        self.buf.clear();
+        if !last {
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| io::Error::other("STREAM chunk counter overflow"))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for StreamCryptoWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        while !buf.is_empty() {
+            let take = (STREAM_CHUNK_SIZE - self.buf.len()).min(buf.len());
+            self.buf.extend_from_slice(&buf[..take]);
+            buf = &buf[take..];
+            if self.buf.len() == STREAM_CHUNK_SIZE {
+                self.seal_and_write_chunk(false)?;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Send + Sync> CryptoWriter<W> for StreamCryptoWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            self.seal_and_write_chunk(true)?;
+            self.inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates a writer that encrypts everything written to it with `cipher`
+/// before forwarding the ciphertext to `inner`.
+///
+/// Low-level building block: the caller must supply a `nonce_seed` that is
+/// unique for this plaintext under `key` (see [`derive_stream_key`]) and
+/// must hand the reader that exact same seed back, out of band, to decrypt
+/// it. Prefer [`create_tmp_file_writer`], which picks and records a fresh
+/// seed itself, unless you have a reason (like content-addressed storage)
+/// to control the seed directly.
+pub fn create_writer<W: Write>(
+    inner: W,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    nonce_seed: u64,
+) -> StreamCryptoWriter<W> {
+    StreamCryptoWriter::new(inner, cipher, &key, nonce_seed)
+}
+
+/// A [`CryptoWriter`] that stages ciphertext into a temporary file and
+/// atomically renames it into place on `finish`, so a reader never observes
+/// a partially-written final file.
+struct FileCryptoWriter {
+    tmp_path: PathBuf,
+    final_path: PathBuf,
+    inner: StreamCryptoWriter<File>,
+    #[allow(dead_code)]
+    lock: Option<Arc<RwLock<bool>>>,
+    finished: bool,
+}
+
+impl Write for FileCryptoWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl CryptoWriter<File> for FileCryptoWriter {
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.inner.finish()?;
+        if let Some(recorder) = self.inner.take_checksums() {
+            let tmp_sidecar = sidecar_path(&self.tmp_path);
+            let mut sidecar_file = File::create(&tmp_sidecar)?;
+            recorder.write_to(&mut sidecar_file)?;
+            sidecar_file.flush()?;
+            fs::rename(&tmp_sidecar, sidecar_path(&self.final_path))?;
+        }
+        fs::rename(&self.tmp_path, &self.final_path)?;
+        Ok(())
+    }
+}
+
+/// Writes the preamble every `FileCryptoWriter` stream starts with, ahead of
+/// the STREAM cipher-id header: the randomly generated `nonce_seed` this
+/// file's STREAM subkey was derived from (so a reader never has to be
+/// trusted to supply the right one), followed by an optional KDF header
+/// (empty when the caller already derived `key` itself).
+fn write_preamble(file: &mut File, nonce_seed: u64, kdf_header: &[u8]) -> io::Result<()> {
+    file.write_all(&nonce_seed.to_le_bytes())?;
+    file.write_all(&(u32::try_from(kdf_header.len()).unwrap()).to_le_bytes())?;
+    file.write_all(kdf_header)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_tmp_file_writer_inner(
+    file_path: &Path,
+    tmp_dir: &Path,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    kdf_header: Vec<u8>,
+    lock: Option<Arc<RwLock<bool>>>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+) -> Result<Box<dyn CryptoWriter<File>>> {
+    let nonce_seed = rand::thread_rng().gen::<u64>();
+    fs::create_dir_all(tmp_dir)?;
+    let tmp_path = tmp_dir.join(format!(
+        "{}.{}.tmp",
+        file_path.file_name().unwrap().to_string_lossy(),
+        uuid_like(nonce_seed)
+    ));
+    let mut tmp_file = File::create(&tmp_path)?;
+    write_preamble(&mut tmp_file, nonce_seed, &kdf_header)?;
+    let mut inner = create_writer(tmp_file, cipher, key, nonce_seed);
+    if let Some(algorithm) = checksum_algorithm {
+        inner.enable_checksums(algorithm);
+    }
+    Ok(Box::new(FileCryptoWriter {
+        tmp_path,
+        final_path: file_path.to_path_buf(),
+        inner,
+        lock,
+        finished: false,
+    }))
+}
+
+/// Creates a writer that encrypts `file_path`'s contents with `cipher`,
+/// staging into a temporary file under `tmp_dir` and renaming into place on
+/// `finish`. When `checksum_algorithm` is `Some`, a per-chunk checksum
+/// sidecar (`<file_path>.sums`) is written alongside the ciphertext for
+/// `create_file_reader` to verify against as it decrypts.
+///
+/// The STREAM nonce seed is generated randomly here, not taken from the
+/// caller, and is recorded in a small preamble ahead of the ciphertext so
+/// `create_file_reader` can recover it: unlike [`create_writer`], there's no
+/// seed for a caller to accidentally reuse across files.
+#[allow(clippy::too_many_arguments)]
+pub fn create_tmp_file_writer(
+    file_path: &Path,
+    tmp_dir: &Path,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    _capacity: Option<usize>,
+    lock: Option<Arc<RwLock<bool>>>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+) -> Result<Box<dyn CryptoWriter<File>>> {
+    create_tmp_file_writer_inner(file_path, tmp_dir, cipher, key, Vec::new(), lock, checksum_algorithm)
+}
+
+/// Creates a writer like [`create_tmp_file_writer`], but derives its key
+/// from `password` with `kdf` instead of taking an already-derived key. The
+/// resulting KDF header (algorithm, cost parameters, salt; see
+/// [`super::kdf::derive_key_with_header`]) is recorded in the same preamble
+/// as the random nonce seed, so `create_file_reader_with_password` can
+/// reconstruct the key from the password alone.
+#[allow(clippy::too_many_arguments)]
+pub fn create_tmp_file_writer_with_password(
+    file_path: &Path,
+    tmp_dir: &Path,
+    cipher: Cipher,
+    password: &secrecy::SecretString,
+    kdf: super::kdf::Kdf,
+    lock: Option<Arc<RwLock<bool>>>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+) -> Result<Box<dyn CryptoWriter<File>>> {
+    let (key, kdf_header) = super::kdf::derive_key_with_header(password, kdf, cipher.key_len())?;
+    create_tmp_file_writer_inner(
+        file_path,
+        tmp_dir,
+        cipher,
+        Arc::new(key),
+        kdf_header,
+        lock,
+        checksum_algorithm,
+    )
+}
+
+/// Writer that splits its input into content-defined (or fixed-size) chunks
+/// and hands each one to a [`ChunkStore`] keyed by its BLAKE3 content id:
+/// re-encrypting a file that shares chunks with a previous encryption into
+/// the same `dir_path` reuses the existing encrypted chunks instead of
+/// writing them again. `finish` records the ordered sequence of chunk ids
+/// making up the file in an `index` file alongside the store, which
+/// `create_chunked_file_reader` walks to reassemble the plaintext.
+pub struct ChunkedFileCryptoWriter {
+    dir_path: PathBuf,
+    store: ChunkStore,
+    tmp_dir: PathBuf,
+    tmp_index_path: PathBuf,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    chunker: Chunker,
+    buf: Vec<u8>,
+    /// Each content-defined chunk's id alongside its plaintext length, the
+    /// latter needed by `create_chunked_file_reader` to know how many
+    /// STREAM-sub-chunk checksum entries belong to it.
+    index: Vec<(ChunkId, u64)>,
+    checksums: Option<ChecksumRecorder>,
+    chunks_stored: u64,
+    chunks_deduped: u64,
+    #[allow(dead_code)]
+    lock: Option<Arc<RwLock<bool>>>,
+    finished: bool,
+}
+
+impl ChunkedFileCryptoWriter {
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        if let Some(recorder) = &mut self.checksums {
+            // Record at the same granularity `create_chunked_file_reader`
+            // will verify at: one entry per STREAM sub-chunk the inner
+            // per-chunk `StreamCryptoWriter` will actually seal, not one
+            // entry for the whole (potentially much larger) content-defined
+            // chunk.
+            let mut offset = 0;
+            for len in stream_sub_chunk_lens(self.buf.len()) {
+                recorder.record(&self.buf[offset..offset + len]);
+                offset += len;
+            }
+        }
+        let id = ChunkId::of(&self.buf);
+        if self
+            .store
+            .store(id, &self.buf, self.cipher, &self.key, &self.tmp_dir)?
+        {
+            self.chunks_stored += 1;
+        } else {
+            self.chunks_deduped += 1;
+        }
+        self.index.push((id, self.buf.len() as u64));
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Returns `(chunks_stored, chunks_deduped)`: how many chunks this
+    /// writer actually encrypted and wrote versus how many it recognized
+    /// from the store and merely referenced.
+    pub fn dedup_stats(&self) -> (u64, u64) {
+        (self.chunks_stored, self.chunks_deduped)
+    }
+}
+
+impl Write for ChunkedFileCryptoWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.buf.push(byte);
+            if self.chunker.push(byte) {
+                self.chunker.start_new_chunk();
+                self.flush_chunk()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CryptoWriter<File> for ChunkedFileCryptoWriter {
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.flush_chunk()?;
+        let mut index_file = File::create(&self.tmp_index_path)?;
+        for (id, len) in &self.index {
+            writeln!(index_file, "{} {len}", id.to_hex())?;
+        }
+        index_file.flush()?;
+        fs::rename(&self.tmp_index_path, self.dir_path.join("index"))?;
+        if let Some(recorder) = self.checksums.take() {
+            let tmp_sidecar = self.tmp_dir.join("checksums.tmp");
+            let mut sidecar_file = File::create(&tmp_sidecar)?;
+            recorder.write_to(&mut sidecar_file)?;
+            sidecar_file.flush()?;
+            fs::rename(&tmp_sidecar, self.dir_path.join("checksums"))?;
+        }
+        let _ = fs::remove_dir_all(&self.tmp_dir);
+        Ok(())
+    }
+}
+
+/// Creates a chunked, deduplicating writer: `dir_path` becomes a
+/// content-addressed store of encrypted chunks (one per content-defined, or
+/// fixed-size, see `chunker`, boundary) plus an `index` file recording the
+/// order they appear in. Pass `chunker` to override the default FastCDC
+/// config. When `checksum_algorithm` is `Some`, a per-chunk checksum sidecar
+/// (`checksums`, alongside `index`) is written for `create_chunked_file_reader`
+/// to verify against as it decrypts.
+#[allow(clippy::too_many_arguments)]
+pub fn create_chunked_tmp_file_writer(
+    dir_path: &Path,
+    tmp_dir: &Path,
+    cipher: Cipher,
+    key: Arc<SecretVec<u8>>,
+    nonce_seed: u64,
+    _capacity: Option<usize>,
+    lock: Option<Arc<RwLock<bool>>>,
+    chunker: Option<ChunkerConfig>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+) -> Result<Box<ChunkedFileCryptoWriter>> {
+    fs::create_dir_all(dir_path)?;
+    let store = ChunkStore::open(dir_path)?;
+    let tmp_dir = tmp_dir.join(format!(
+        "{}.{}.chunks-tmp",
+        dir_path.file_name().unwrap().to_string_lossy(),
+        uuid_like(nonce_seed)
+    ));
+    let tmp_index_path = tmp_dir.join("index.tmp");
+    fs::create_dir_all(&tmp_dir)?;
+    Ok(Box::new(ChunkedFileCryptoWriter {
+        dir_path: dir_path.to_path_buf(),
+        store,
+        tmp_dir,
+        tmp_index_path,
+        cipher,
+        key,
+        chunker: Chunker::new(chunker.unwrap_or_default()),
+        buf: Vec::new(),
+        index: Vec::new(),
+        checksums: checksum_algorithm.map(ChecksumRecorder::new),
+        chunks_stored: 0,
+        chunks_deduped: 0,
+        lock,
+        finished: false,
+    }))
+}
+
+fn uuid_like(seed: u64) -> u64 {
+    seed ^ 0x9E37_79B9_7F4A_7C15
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::crypto::reader::create_reader;
+
+    fn key(byte: u8) -> Arc<SecretVec<u8>> {
+        Arc::new(SecretVec::new(vec![byte; 32]))
+    }
+
+    #[test]
+    fn password_based_file_round_trips_without_a_pre_derived_key() {
+        use secrecy::SecretString;
+
+        use crate::crypto::kdf::Kdf;
+        use crate::crypto::reader::create_file_reader_with_password;
+
+        let dir = std::env::temp_dir().join("rencfs-test-password-file-round-trip");
+        let tmp_dir = dir.join("tmp");
+        let file_path = dir.join("out.enc");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+
+        let password = SecretString::new("correct horse battery staple".to_string());
+        let plaintext = b"some plaintext that needs a password-derived key".to_vec();
+
+        let mut writer = create_tmp_file_writer_with_password(
+            &file_path,
+            &tmp_dir,
+            Cipher::ChaCha20,
+            &password,
+            Kdf::Pbkdf2HmacSha256 { iterations: 1_000 },
+            None,
+            None,
+        )
+        .unwrap();
+        writer.write_all(&plaintext).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader =
+            create_file_reader_with_password(&file_path, Cipher::ChaCha20, &password, None)
+                .unwrap();
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        let wrong_password = SecretString::new("wrong password".to_string());
+        let err = create_file_reader_with_password(
+            &file_path,
+            Cipher::ChaCha20,
+            &wrong_password,
+            None,
+        )
+        .unwrap()
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+        assert!(err.to_string().contains("STREAM"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn round_trips_data_spanning_several_chunks() {
+        let key = key(7);
+        let plaintext = vec![0xAB_u8; STREAM_CHUNK_SIZE * 2 + 123];
+
+        let mut ciphertext = Vec::new();
+        let mut writer = create_writer(&mut ciphertext, Cipher::ChaCha20, key.clone(), 1);
+        writer.write_all(&plaintext).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = create_reader(ciphertext.as_slice(), Cipher::ChaCha20, key, 1);
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn truncated_ciphertext_is_rejected() {
+        let key = key(9);
+        let plaintext = vec![0x11_u8; STREAM_CHUNK_SIZE * 2];
+
+        let mut ciphertext = Vec::new();
+        let mut writer = create_writer(&mut ciphertext, Cipher::ChaCha20, key.clone(), 2);
+        writer.write_all(&plaintext).unwrap();
+        writer.finish().unwrap();
+
+        ciphertext.truncate(ciphertext.len() - 5);
+
+        let mut reader = create_reader(ciphertext.as_slice(), Cipher::ChaCha20, key, 2);
+        let mut decrypted = Vec::new();
+        assert!(reader.read_to_end(&mut decrypted).is_err());
+    }
+
+    #[test]
+    fn reordered_chunks_fail_authentication() {
+        let key = key(11);
+        let plaintext = vec![0x22_u8; STREAM_CHUNK_SIZE * 2];
+
+        let mut ciphertext = Vec::new();
+        let mut writer = create_writer(&mut ciphertext, Cipher::ChaCha20, key.clone(), 3);
+        writer.write_all(&plaintext).unwrap();
+        writer.finish().unwrap();
+
+        // Swap the two full-size chunks (header byte, then one
+        // STREAM_CHUNK_CIPHERTEXT_SIZE chunk each); the nonce is bound to
+        // chunk position, so decrypting chunk 1's ciphertext as chunk 0
+        // must fail authentication rather than silently succeeding.
+        let header = ciphertext[0];
+        let chunk0 = ciphertext[1..1 + STREAM_CHUNK_CIPHERTEXT_SIZE].to_vec();
+        let chunk1_start = 1 + STREAM_CHUNK_CIPHERTEXT_SIZE;
+        let chunk1 = ciphertext[chunk1_start..chunk1_start + STREAM_CHUNK_CIPHERTEXT_SIZE].to_vec();
+        let mut reordered = vec![header];
+        reordered.extend_from_slice(&chunk1);
+        reordered.extend_from_slice(&chunk0);
+        reordered.extend_from_slice(&ciphertext[chunk1_start + STREAM_CHUNK_CIPHERTEXT_SIZE..]);
+
+        let mut reader = create_reader(reordered.as_slice(), Cipher::ChaCha20, key, 3);
+        let mut decrypted = Vec::new();
+        assert!(reader.read_to_end(&mut decrypted).is_err());
+    }
+
+    #[test]
+    fn tampered_chunk_fails_authentication() {
+        let key = key(13);
+        let plaintext = vec![0x33_u8; STREAM_CHUNK_SIZE / 2];
+
+        let mut ciphertext = Vec::new();
+        let mut writer = create_writer(&mut ciphertext, Cipher::ChaCha20, key.clone(), 4);
+        writer.write_all(&plaintext).unwrap();
+        writer.finish().unwrap();
+
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 0xFF;
+
+        let mut reader = create_reader(ciphertext.as_slice(), Cipher::ChaCha20, key, 4);
+        let mut decrypted = Vec::new();
+        assert!(reader.read_to_end(&mut decrypted).is_err());
+    }
+
+    #[test]
+    fn chunked_round_trip_with_chunk_size_above_stream_chunk_size_verifies_checksums() {
+        use crate::crypto::chunker::ChunkerConfig;
+        use crate::crypto::integrity::ChecksumAlgorithm;
+        use crate::crypto::reader::create_chunked_file_reader;
+
+        let dir = std::env::temp_dir().join("rencfs-test-chunked-large-chunk-checksums");
+        let tmp_dir = dir.join("tmp");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+
+        let key = key(17);
+        // Content-defined chunk size (200 KiB) well above STREAM_CHUNK_SIZE
+        // (64 KiB), so each content chunk is sealed as several STREAM
+        // sub-chunks; checksums must be recorded/verified per sub-chunk, not
+        // once for the whole content chunk.
+        let plaintext: Vec<u8> = (0..200 * 1024).map(|i| (i % 251) as u8).collect();
+
+        let mut writer = create_chunked_tmp_file_writer(
+            &dir,
+            &tmp_dir,
+            Cipher::ChaCha20,
+            key.clone(),
+            5,
+            None,
+            None,
+            Some(ChunkerConfig::FixedSize(200 * 1024)),
+            Some(ChecksumAlgorithm::Blake3),
+        )
+        .unwrap();
+        writer.write_all(&plaintext).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = create_chunked_file_reader(&dir, Cipher::ChaCha20, key, 5, None).unwrap();
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}